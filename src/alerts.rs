@@ -0,0 +1,263 @@
+//! Generic threshold-based alert engine. Evaluates the `[alerts]` config
+//! against each refresh's polled values and turns sustained breaches into
+//! the same warning/error strings `check_md_raid` has always produced, so
+//! the LED summary and Messages/Journal pages need no further changes.
+
+use std::collections::BTreeMap;
+
+use crate::config;
+
+/// Consecutive samples a reading must stay past a threshold before the
+/// alert latches, so a transient spike doesn't flap it on `check_md_raid`'s
+/// heels every `REFRESH_INTERVAL` (2s).
+const CONSECUTIVE_SAMPLES: u32 = 3;
+
+/// A single warning/error condition. `key` is a stable identity (rule name,
+/// mount point, or interface name) used to detect raise/clear transitions;
+/// `text` is what's actually displayed and may embed a live value that
+/// changes every refresh, so it must never be used for transition diffing.
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub key: String,
+    pub text: String,
+}
+
+impl Alert {
+    /// For conditions whose formatted text never changes while the
+    /// condition holds (e.g. `check_md_raid`'s), the text itself is a fine
+    /// identity.
+    pub fn stable(text: String) -> Self {
+        Self {
+            key: text.clone(),
+            text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Tracks one named series (e.g. a single disk mount or network interface)
+/// against its warn/crit thresholds, latching only after
+/// `CONSECUTIVE_SAMPLES` readings agree.
+struct Gauge {
+    warn: Option<f32>,
+    crit: Option<f32>,
+    latched: Severity,
+    pending: Severity,
+    pending_count: u32,
+}
+
+impl Gauge {
+    fn new(warn: Option<f32>, crit: Option<f32>) -> Self {
+        Self {
+            warn,
+            crit,
+            latched: Severity::Ok,
+            pending: Severity::Ok,
+            pending_count: 0,
+        }
+    }
+
+    /// Pre-latch this gauge to `severity`, as if it had already seen
+    /// `CONSECUTIVE_SAMPLES` consecutive readings past the matching
+    /// threshold. Used to seed a gauge from a journal condition that was
+    /// still open across a restart, so `Engine::evaluate`'s first call
+    /// doesn't report it absent (and thus spuriously `Cleared`) while the
+    /// gauge re-accumulates fresh samples.
+    fn seed(&mut self, severity: Severity) {
+        self.latched = severity;
+        self.pending = severity;
+        self.pending_count = CONSECUTIVE_SAMPLES;
+    }
+
+    fn sample(&mut self, value: f32) -> Severity {
+        let raw = if self.crit.is_some_and(|t| value >= t) {
+            Severity::Error
+        } else if self.warn.is_some_and(|t| value >= t) {
+            Severity::Warning
+        } else {
+            Severity::Ok
+        };
+
+        if raw == self.pending {
+            self.pending_count += 1;
+        } else {
+            self.pending = raw;
+            self.pending_count = 1;
+        }
+
+        if self.pending_count >= CONSECUTIVE_SAMPLES {
+            self.latched = raw;
+        }
+
+        self.latched
+    }
+}
+
+pub struct Engine {
+    config: config::Alerts,
+    load1: Gauge,
+    mem_used: Gauge,
+    disks: BTreeMap<String, Gauge>,
+    net: BTreeMap<String, Gauge>,
+    /// Keys of journal conditions still open at startup, and the severity
+    /// each one's gauge should be pre-latched to. Only consulted when a
+    /// `disks`/`net` gauge is first created in `evaluate`; see `Gauge::seed`.
+    initial_seed: BTreeMap<String, Severity>,
+}
+
+impl Engine {
+    /// Build a fresh engine, pre-latching `load1`/`mem_used` (and seeding
+    /// `disks`/`net` gauges as they're created in `evaluate`) for any key
+    /// present in `open_warnings`/`open_errors` — the journal's still-open
+    /// conditions from before this restart. Without this, a gauge-based
+    /// condition that was genuinely still active would start `Ok` and take
+    /// `CONSECUTIVE_SAMPLES` refreshes to re-latch, and `diff_alerts` would
+    /// log a spurious `Cleared` in the meantime.
+    pub fn new(config: config::Alerts, open_warnings: &[Alert], open_errors: &[Alert]) -> Self {
+        let mut initial_seed: BTreeMap<String, Severity> = BTreeMap::new();
+        for alert in open_warnings {
+            initial_seed.insert(alert.key.clone(), Severity::Warning);
+        }
+        for alert in open_errors {
+            initial_seed.insert(alert.key.clone(), Severity::Error);
+        }
+
+        let mut load1 = Gauge::new(config.load1_warn, config.load1_crit);
+        if let Some(&severity) = initial_seed.get("load1") {
+            load1.seed(severity);
+        }
+        let mut mem_used = Gauge::new(config.mem_used_warn, config.mem_used_crit);
+        if let Some(&severity) = initial_seed.get("mem") {
+            mem_used.seed(severity);
+        }
+
+        Self {
+            load1,
+            mem_used,
+            disks: BTreeMap::new(),
+            net: BTreeMap::new(),
+            initial_seed,
+            config,
+        }
+    }
+
+    /// Evaluate this refresh's readings, pushing an `Alert` into
+    /// `warnings`/`errors` for any gauge currently latched past its warn or
+    /// crit threshold. `disk_used_frac`/`net_mbps` are `0..1` and absolute
+    /// Mbps respectively, matching the units of the config thresholds.
+    ///
+    /// Each `Alert`'s `key` is the rule/mount/interface name alone, never
+    /// the live value in `text` — callers (namely `diff_alerts`) rely on
+    /// `key` staying put for as long as the underlying condition does, even
+    /// though `text` changes every refresh.
+    pub fn evaluate(
+        &mut self,
+        load1: f32,
+        mem_used_frac: f32,
+        disk_used_frac: impl IntoIterator<Item = (String, f32)>,
+        net_mbps: impl IntoIterator<Item = (String, f32)>,
+        warnings: &mut Vec<Alert>,
+        errors: &mut Vec<Alert>,
+    ) {
+        push(
+            warnings,
+            errors,
+            self.load1.sample(load1),
+            "load1",
+            || format!("load1 {load1:.2}"),
+        );
+        push(
+            warnings,
+            errors,
+            self.mem_used.sample(mem_used_frac),
+            "mem",
+            || format!("mem {:.0}% used", mem_used_frac * 100.0),
+        );
+
+        let (disk_warn, disk_crit) = (self.config.disk_used_warn, self.config.disk_used_crit);
+        for (mount, used_frac) in disk_used_frac {
+            let seed = self.initial_seed.get(&mount).copied();
+            let gauge = self.disks.entry(mount.clone()).or_insert_with(|| {
+                let mut gauge = Gauge::new(disk_warn, disk_crit);
+                if let Some(severity) = seed {
+                    gauge.seed(severity);
+                }
+                gauge
+            });
+            push(warnings, errors, gauge.sample(used_frac), &mount, || {
+                format!("{mount} {:.0}% full", used_frac * 100.0)
+            });
+        }
+
+        let (net_warn, net_crit) = (self.config.net_mbps_warn, self.config.net_mbps_crit);
+        for (iface, mbps) in net_mbps {
+            let seed = self.initial_seed.get(&iface).copied();
+            let gauge = self.net.entry(iface.clone()).or_insert_with(|| {
+                let mut gauge = Gauge::new(net_warn, net_crit);
+                if let Some(severity) = seed {
+                    gauge.seed(severity);
+                }
+                gauge
+            });
+            push(warnings, errors, gauge.sample(mbps), &iface, || {
+                format!("{iface} {mbps:.0} Mbps")
+            });
+        }
+    }
+}
+
+fn push(
+    warnings: &mut Vec<Alert>,
+    errors: &mut Vec<Alert>,
+    severity: Severity,
+    key: &str,
+    text: impl FnOnce() -> String,
+) {
+    let alert = || Alert {
+        key: key.to_owned(),
+        text: text(),
+    };
+    match severity {
+        Severity::Error => errors.push(alert()),
+        Severity::Warning => warnings.push(alert()),
+        Severity::Ok => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gauge_latches_only_after_consecutive_breaches() {
+        let mut gauge = Gauge::new(Some(80.0), Some(95.0));
+
+        assert_eq!(gauge.sample(50.0), Severity::Ok);
+
+        // A breach doesn't latch until it's been seen CONSECUTIVE_SAMPLES
+        // times in a row.
+        for _ in 0..CONSECUTIVE_SAMPLES - 1 {
+            assert_eq!(gauge.sample(90.0), Severity::Ok);
+        }
+        assert_eq!(gauge.sample(90.0), Severity::Warning);
+
+        // Crossing into the next severity band needs its own fresh run of
+        // CONSECUTIVE_SAMPLES before it latches Error...
+        for _ in 0..CONSECUTIVE_SAMPLES - 1 {
+            assert_eq!(gauge.sample(99.0), Severity::Warning);
+        }
+        assert_eq!(gauge.sample(99.0), Severity::Error);
+
+        // ...and unlatches the same way.
+        for _ in 0..CONSECUTIVE_SAMPLES - 1 {
+            assert_eq!(gauge.sample(10.0), Severity::Error);
+        }
+        assert_eq!(gauge.sample(10.0), Severity::Ok);
+    }
+}
@@ -5,6 +5,17 @@ pub struct Config {
     pub lcd: Lcd,
     pub disk: Disk,
     pub network: Network,
+    /// Absent unless a `[exporter]` section is configured; the Prometheus
+    /// exporter is opt-in.
+    #[serde(default)]
+    pub exporter: Option<Exporter>,
+    /// Absent unless a `[journal]` section is configured; without it, the
+    /// alert journal is kept in memory only and does not survive restarts.
+    #[serde(default)]
+    pub journal: Option<Journal>,
+    /// All thresholds are optional; an unset threshold never alerts.
+    #[serde(default)]
+    pub alerts: Alerts,
 }
 
 #[derive(Deserialize)]
@@ -23,4 +34,52 @@ pub struct Disk {
 #[derive(Deserialize)]
 pub struct Network {
     pub interfaces: Vec<String>,
+    /// Link speed in Mbps, used as the full-scale ceiling for the network
+    /// history sparkline. Defaults to a typical gigabit link.
+    #[serde(default = "default_link_mbps")]
+    pub link_mbps: f32,
+}
+
+fn default_link_mbps() -> f32 {
+    1000.0
+}
+
+#[derive(Deserialize)]
+pub struct Exporter {
+    pub listen: String,
+}
+
+#[derive(Deserialize)]
+pub struct Journal {
+    pub path: String,
+    #[serde(default = "default_journal_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_journal_max_entries() -> usize {
+    200
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+pub struct Alerts {
+    /// 1-minute load average thresholds.
+    #[serde(default)]
+    pub load1_warn: Option<f32>,
+    #[serde(default)]
+    pub load1_crit: Option<f32>,
+    /// Fraction (`0..1`) of memory in use.
+    #[serde(default)]
+    pub mem_used_warn: Option<f32>,
+    #[serde(default)]
+    pub mem_used_crit: Option<f32>,
+    /// Fraction (`0..1`) of disk space in use, per mount.
+    #[serde(default)]
+    pub disk_used_warn: Option<f32>,
+    #[serde(default)]
+    pub disk_used_crit: Option<f32>,
+    /// Absolute throughput in Mbps, per interface.
+    #[serde(default)]
+    pub net_mbps_warn: Option<f32>,
+    #[serde(default)]
+    pub net_mbps_crit: Option<f32>,
 }
@@ -0,0 +1,119 @@
+//! Optional `GET /metrics` TCP listener that exposes the metrics `refresh()`
+//! already collects in Prometheus text exposition format, so the box can be
+//! scraped without standing in front of the LCD.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write as _},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+
+/// Point-in-time copy of the metrics the display loop has most recently
+/// polled. The exporter thread only ever reads this; it never touches
+/// `System` directly, so the two never fight over it.
+#[derive(Default, Clone)]
+pub struct Snapshot {
+    pub load1: f32,
+    pub load5: f32,
+    pub load15: f32,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+    pub disk_used_bytes: BTreeMap<String, u64>,
+    pub disk_total_bytes: BTreeMap<String, u64>,
+    pub net_rx_bytes: BTreeMap<String, u64>,
+    pub net_tx_bytes: BTreeMap<String, u64>,
+    pub mdraid_degraded: BTreeMap<String, bool>,
+}
+
+pub type SharedSnapshot = Arc<Mutex<Snapshot>>;
+
+/// A client that connects but never sends a request (or stalls mid-write on
+/// the response) shouldn't be able to wedge a scrape forever.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Spawn the listener on its own thread. Runs until the process exits. Each
+/// connection is handled on its own thread so one slow client can't delay
+/// scrapes from anyone else.
+pub fn spawn(listen: String, snapshot: SharedSnapshot) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(&listen)
+        .with_context(|| format!("failed to bind exporter listener on {listen}"))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let snapshot = Arc::clone(&snapshot);
+            std::thread::spawn(move || handle_connection(stream, &snapshot));
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: std::net::TcpStream, snapshot: &SharedSnapshot) {
+    let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+
+    let mut request_line = String::new();
+    let mut reader = match stream.try_clone() {
+        Ok(clone) => BufReader::new(clone),
+        Err(_) => return,
+    };
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+
+    let response = if request_line.starts_with("GET /metrics") {
+        let body = render(&snapshot.lock().unwrap());
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body,
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_owned()
+    };
+
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn render(snapshot: &Snapshot) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "serverhud_load1 {}", snapshot.load1);
+    let _ = writeln!(out, "serverhud_load5 {}", snapshot.load5);
+    let _ = writeln!(out, "serverhud_load15 {}", snapshot.load15);
+    let _ = writeln!(
+        out,
+        "serverhud_memory_used_bytes {}",
+        snapshot.memory_used_bytes
+    );
+    let _ = writeln!(
+        out,
+        "serverhud_memory_total_bytes {}",
+        snapshot.memory_total_bytes
+    );
+    for (mount, used) in &snapshot.disk_used_bytes {
+        let _ = writeln!(out, "serverhud_disk_used_bytes{{mount=\"{mount}\"}} {used}");
+    }
+    for (mount, total) in &snapshot.disk_total_bytes {
+        let _ = writeln!(out, "serverhud_disk_total_bytes{{mount=\"{mount}\"}} {total}");
+    }
+    for (iface, rx) in &snapshot.net_rx_bytes {
+        let _ = writeln!(out, "serverhud_net_bytes{{iface=\"{iface}\",dir=\"rx\"}} {rx}");
+    }
+    for (iface, tx) in &snapshot.net_tx_bytes {
+        let _ = writeln!(out, "serverhud_net_bytes{{iface=\"{iface}\",dir=\"tx\"}} {tx}");
+    }
+    for (dev, degraded) in &snapshot.mdraid_degraded {
+        let _ = writeln!(
+            out,
+            "serverhud_mdraid_degraded{{dev=\"{dev}\"}} {}",
+            *degraded as u8
+        );
+    }
+    out
+}
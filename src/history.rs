@@ -0,0 +1,95 @@
+//! Rolling sample history for the `Page::History` sparkline row, rendered
+//! with CFA635 CGRAM glyphs instead of plotting pixels directly.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use cfa635::NUM_COLUMNS;
+
+/// Number of rolling samples kept per metric, one per `REFRESH_INTERVAL`.
+const HISTORY_LEN: usize = NUM_COLUMNS as usize;
+
+/// Number of CGRAM glyphs programmed for bar heights 1..=8.
+const NUM_GLYPH_LEVELS: u8 = 8;
+
+/// Per-interface up/down throughput history.
+#[derive(Default)]
+pub struct NetHistory {
+    pub up: VecDeque<f32>,
+    pub down: VecDeque<f32>,
+}
+
+pub struct History {
+    pub load: VecDeque<f32>,
+    pub mem_used_frac: VecDeque<f32>,
+    pub net: BTreeMap<String, NetHistory>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            load: VecDeque::with_capacity(HISTORY_LEN),
+            mem_used_frac: VecDeque::with_capacity(HISTORY_LEN),
+            net: BTreeMap::new(),
+        }
+    }
+
+    pub fn push_load(&mut self, value: f32) {
+        push_capped(&mut self.load, value);
+    }
+
+    pub fn push_mem_used_frac(&mut self, value: f32) {
+        push_capped(&mut self.mem_used_frac, value);
+    }
+
+    pub fn push_net(&mut self, iface: &str, up: f32, down: f32) {
+        let entry = self.net.entry(iface.to_owned()).or_default();
+        push_capped(&mut entry.up, up);
+        push_capped(&mut entry.down, down);
+    }
+}
+
+fn push_capped(buf: &mut VecDeque<f32>, value: f32) {
+    if buf.len() == HISTORY_LEN {
+        buf.pop_front();
+    }
+    buf.push_back(value);
+}
+
+/// Program the CFA635's 8 user-definable CGRAM glyphs with vertical bars of
+/// pixel height 1..=8 (glyph `k` lights the bottom `k` rows of the 6x8
+/// cell), so a single glyph byte can render one sparkline column.
+pub fn program_glyphs(lcd: &mut cfa635::Device) -> anyhow::Result<()> {
+    for level in 1..=NUM_GLYPH_LEVELS {
+        let mut glyph = [0u8; 8];
+        for row in glyph.iter_mut().skip((NUM_GLYPH_LEVELS - level) as usize) {
+            *row = 0b11_1111;
+        }
+        lcd.set_custom_character(level - 1, glyph)?;
+    }
+    Ok(())
+}
+
+/// Normalize `value` against `max` into a glyph byte: a blank for level 0,
+/// otherwise the CGRAM index programmed by `program_glyphs`.
+fn level_glyph(value: f32, max: f32) -> u8 {
+    if max <= 0.0 {
+        return b' ';
+    }
+    let level = ((value / max).clamp(0.0, 1.0) * NUM_GLYPH_LEVELS as f32).round() as u8;
+    if level == 0 {
+        b' '
+    } else {
+        level - 1
+    }
+}
+
+/// Render a ring of samples as a row of glyph bytes, right-aligned so the
+/// newest sample lands in the last column.
+pub fn render_row(samples: &VecDeque<f32>, max: f32) -> [u8; HISTORY_LEN] {
+    let mut row = [b' '; HISTORY_LEN];
+    let offset = HISTORY_LEN - samples.len();
+    for (i, &value) in samples.iter().enumerate() {
+        row[offset + i] = level_glyph(value, max);
+    }
+    row
+}
@@ -0,0 +1,217 @@
+//! Persistent, bounded journal of warning/error transitions, so the
+//! Messages page has a record of events instead of only steady-state
+//! strings that vanish on acknowledge or restart.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+
+use crate::{alerts::Alert, config};
+
+const DEFAULT_MAX_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Raised,
+    Cleared,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub timestamp: u64,
+    pub severity: Severity,
+    pub transition: Transition,
+    /// The `Alert::key` this transition applies to; stable across refreshes
+    /// even when `text` isn't, so a reload can tell which conditions are
+    /// still open.
+    pub key: String,
+    pub text: String,
+}
+
+impl Entry {
+    fn write_line(&self, out: &mut String) {
+        let severity = match self.severity {
+            Severity::Warning => 'W',
+            Severity::Error => 'E',
+        };
+        let transition = match self.transition {
+            Transition::Raised => '+',
+            Transition::Cleared => '-',
+        };
+        out.push_str(&format!(
+            "{}\t{severity}\t{transition}\t{}\t{}\n",
+            self.timestamp, self.key, self.text
+        ));
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(5, '\t');
+        let timestamp: u64 = parts.next()?.parse().ok()?;
+        let severity = match parts.next()? {
+            "W" => Severity::Warning,
+            "E" => Severity::Error,
+            _ => return None,
+        };
+        let transition = match parts.next()? {
+            "+" => Transition::Raised,
+            "-" => Transition::Cleared,
+            _ => return None,
+        };
+        let key = parts.next()?.to_owned();
+        let text = parts.next()?.to_owned();
+        Some(Self {
+            timestamp,
+            severity,
+            transition,
+            key,
+            text,
+        })
+    }
+}
+
+/// A bounded ring of `Entry`, mirrored to disk (if configured) so it
+/// survives process restarts. Oldest entries are dropped once `max_entries`
+/// is exceeded.
+pub struct Journal {
+    path: Option<PathBuf>,
+    max_entries: usize,
+    entries: VecDeque<Entry>,
+}
+
+impl Journal {
+    pub fn open(config: Option<&config::Journal>) -> anyhow::Result<Self> {
+        match config {
+            Some(config) => {
+                let path = PathBuf::from(&config.path);
+                let mut entries: VecDeque<Entry> = if path.exists() {
+                    std::fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read journal {}", path.display()))?
+                        .lines()
+                        .filter_map(Entry::parse)
+                        .collect()
+                } else {
+                    VecDeque::new()
+                };
+                while entries.len() > config.max_entries {
+                    entries.pop_front();
+                }
+                Ok(Self {
+                    path: Some(path),
+                    max_entries: config.max_entries,
+                    entries,
+                })
+            }
+            None => Ok(Self {
+                path: None,
+                max_entries: DEFAULT_MAX_ENTRIES,
+                entries: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Append an entry to the in-memory ring. This does not touch disk;
+    /// call `flush` once after a batch of related `record` calls (e.g. all
+    /// the transitions from one refresh) so they're written out in a
+    /// single pass instead of rewriting the whole file per transition.
+    pub fn record(
+        &mut self,
+        severity: Severity,
+        transition: Transition,
+        key: String,
+        text: String,
+    ) {
+        self.entries.push_back(Entry {
+            timestamp: unix_timestamp(),
+            severity,
+            transition,
+            key,
+            text,
+        });
+        while self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Conditions that are still open as of the loaded journal tail, i.e.
+    /// keys whose last `Raised` has no later `Cleared` of the same severity.
+    /// Used to seed
+    /// `App::prev_warnings`/`prev_errors` on startup so a condition that was
+    /// already alerting before a restart isn't logged as a fresh `Raised`
+    /// with no `Cleared` in between.
+    pub fn open_conditions(&self) -> (Vec<Alert>, Vec<Alert>) {
+        let mut open: BTreeMap<&str, (Severity, &str)> = BTreeMap::new();
+        for entry in &self.entries {
+            match entry.transition {
+                Transition::Raised => {
+                    open.insert(&entry.key, (entry.severity, &entry.text));
+                }
+                Transition::Cleared => {
+                    // An escalation logs the new severity's `Raised` before
+                    // the old one's `Cleared`; only a matching `Cleared`
+                    // closes the condition.
+                    if open
+                        .get(entry.key.as_str())
+                        .is_some_and(|(severity, _)| *severity == entry.severity)
+                    {
+                        open.remove(entry.key.as_str());
+                    }
+                }
+            }
+        }
+
+        let mut warnings = Vec::new();
+        let mut errors = Vec::new();
+        for (key, (severity, text)) in open {
+            let alert = Alert {
+                key: key.to_owned(),
+                text: text.to_owned(),
+            };
+            match severity {
+                Severity::Warning => warnings.push(alert),
+                Severity::Error => errors.push(alert),
+            }
+        }
+        (warnings, errors)
+    }
+
+    /// Write the current entries to disk, if a path is configured. Writes
+    /// to a temp file alongside the real one and renames it into place, so
+    /// a crash mid-write leaves the previous journal intact instead of a
+    /// truncated one.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut out = String::new();
+        for entry in &self.entries {
+            entry.write_line(&mut out);
+        }
+        let tmp_path = PathBuf::from(format!("{}.tmp", path.display()));
+        std::fs::write(&tmp_path, out)
+            .with_context(|| format!("failed to write journal {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("failed to replace journal {}", path.display()))
+    }
+
+    /// Entries oldest-first; callers wanting newest-first should `.rev()`.
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -1,20 +1,31 @@
+mod alerts;
 pub mod config;
+mod exporter;
+mod history;
+mod journal;
 
 use std::{
     collections::BTreeMap,
     path::Path,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
 use anyhow::Context;
 use cfa635::{Key, Report, NUM_COLUMNS, NUM_ROWS};
 use config::Config;
+use alerts::Alert;
+use history::History;
 use sysinfo::{Disk, DiskExt, NetworkData, NetworkExt, NetworksExt, System, SystemExt};
 
 const POLL_INTERVAL: Duration = Duration::from_millis(10);
 const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
 const SCREEN_TIMEOUT: Duration = Duration::from_secs(15);
 
+// Not a byte `set_text` ever actually sends, so filling `displayed` with it
+// guarantees the next `flush` treats every cell as changed.
+const DISPLAYED_SENTINEL: u8 = 0xFF;
+
 struct App {
     config: Config,
     system: System,
@@ -27,9 +38,29 @@ struct App {
     scroll: usize,
     max_scroll: Option<usize>,
     buffer: [[u8; NUM_COLUMNS as usize]; NUM_ROWS as usize],
+    // What the LCD actually has on-screen right now, so `flush` can send
+    // only the cells that changed instead of the whole panel every time.
+    displayed: [[u8; NUM_COLUMNS as usize]; NUM_ROWS as usize],
+
+    history: History,
+    // Index into `history_sources()`, changed by Up/Down while on
+    // `Page::History` instead of the usual scrolling.
+    history_metric: usize,
+
+    // Read by the exporter thread (if enabled); written by `refresh()`.
+    snapshot: exporter::SharedSnapshot,
 
-    md_warnings: Vec<String>,
-    md_errors: Vec<String>,
+    journal: journal::Journal,
+    // The warning/error sets as of the previous refresh, keyed by
+    // `Alert::key` so `diff_alerts` can tell a steady-state condition from
+    // a fresh transition even though `Alert::text` churns every refresh.
+    prev_warnings: Vec<Alert>,
+    prev_errors: Vec<Alert>,
+
+    alerts: alerts::Engine,
+
+    warnings: Vec<Alert>,
+    errors: Vec<Alert>,
 }
 
 impl App {
@@ -47,6 +78,14 @@ impl App {
             ],
             &[],
         )?;
+        history::program_glyphs(&mut lcd)?;
+        let journal = journal::Journal::open(config.journal.as_ref())?;
+        // Seed `prev_*` from whatever the journal's loaded tail shows as
+        // still open, so a condition that was already raised before a
+        // restart isn't logged as a second `Raised` with no `Cleared` in
+        // between.
+        let (prev_warnings, prev_errors) = journal.open_conditions();
+        let alerts = alerts::Engine::new(config.alerts, &prev_warnings, &prev_errors);
         let system = System::new();
         let now = Instant::now();
         Ok(Self {
@@ -61,8 +100,16 @@ impl App {
             scroll: 0,
             max_scroll: None,
             buffer: [[b' '; NUM_COLUMNS as usize]; NUM_ROWS as usize],
-            md_warnings: Vec::new(),
-            md_errors: Vec::new(),
+            displayed: [[DISPLAYED_SENTINEL; NUM_COLUMNS as usize]; NUM_ROWS as usize],
+            history: History::new(),
+            history_metric: 0,
+            snapshot: Arc::new(Mutex::new(exporter::Snapshot::default())),
+            journal,
+            prev_warnings,
+            prev_errors,
+            alerts,
+            warnings: Vec::new(),
+            errors: Vec::new(),
         })
     }
 
@@ -113,6 +160,12 @@ impl App {
                             Key::Right => {
                                 self.set_page(self.current_page.next());
                             }
+                            Key::Up if self.current_page == Page::History => {
+                                self.history_prev_metric();
+                            }
+                            Key::Down if self.current_page == Page::History => {
+                                self.history_next_metric();
+                            }
                             Key::Up => {
                                 if self.scroll > 0 {
                                     self.scroll -= 1;
@@ -127,10 +180,17 @@ impl App {
                                     }
                                 }
                             }
-                            Key::Enter | Key::Exit if self.current_page == Page::Messages => {
+                            Key::Exit if self.current_page == Page::Messages => {
                                 // Acknowledge messages.
                                 self.set_page(Page::System);
                             }
+                            Key::Enter if self.current_page == Page::Messages => {
+                                // Drill into the full alert history.
+                                self.set_page(Page::Journal);
+                            }
+                            Key::Exit if self.current_page == Page::Journal => {
+                                self.set_page(Page::System);
+                            }
                             _ => {}
                         }
                     }
@@ -151,6 +211,10 @@ impl App {
     fn sleep(&mut self) -> anyhow::Result<()> {
         self.screen_timeout = None;
         self.lcd.set_backlight(0, 0)?;
+        // The panel's contents are about to go dark and stale; invalidate
+        // `displayed` so the first redraw after wake resends everything
+        // instead of diffing against what was on screen before sleeping.
+        self.displayed = [[DISPLAYED_SENTINEL; NUM_COLUMNS as usize]; NUM_ROWS as usize];
         Ok(())
     }
 
@@ -159,7 +223,7 @@ impl App {
         self.screen_timeout = Some(Instant::now() + SCREEN_TIMEOUT);
         if was_asleep {
             // If we have messages to display, then start in messages page.
-            if !self.md_warnings.is_empty() || !self.md_errors.is_empty() {
+            if !self.warnings.is_empty() || !self.errors.is_empty() {
                 self.set_page(Page::Messages);
             }
 
@@ -191,9 +255,13 @@ impl App {
         self.system.refresh_disks_list();
         self.system.refresh_networks_list();
 
+        self.sample_history();
         self.check_md_raid();
+        self.evaluate_alerts();
+        self.diff_alerts();
+        self.update_snapshot();
 
-        if self.md_warnings.is_empty() && self.md_errors.is_empty() {
+        if self.warnings.is_empty() && self.errors.is_empty() {
             // 1x green: idle, OK
             self.lcd.set_led(0, 0, 100).ok();
             self.lcd.set_led(1, 0, 0).ok();
@@ -201,10 +269,10 @@ impl App {
             self.lcd.set_led(3, 0, 0).ok();
         } else {
             // Errors indicated by 1 red LED
-            let errors = (0..self.md_errors.len()).map(|_| (100, 0));
+            let errors = (0..self.errors.len()).map(|_| (100, 0));
 
             // Warnings indicated by 1 yellow LED
-            let warnings = (0..self.md_warnings.len()).map(|_| (100, 100));
+            let warnings = (0..self.warnings.len()).map(|_| (100, 100));
 
             // Turn the remaining LEDs off.
             let default = std::iter::repeat((0, 0));
@@ -232,13 +300,13 @@ impl App {
     }
 
     fn check_md_raid(&mut self) {
-        self.md_warnings = Vec::new();
-        self.md_errors = Vec::new();
+        self.warnings = Vec::new();
+        self.errors = Vec::new();
 
         for dev in self.config.disk.md_raid.clone() {
             if let Err(e) = self.check_md_dev(&dev) {
                 eprintln!("{dev}: query error: {e}");
-                self.md_warnings.push(format!("{dev}: query error"));
+                self.warnings.push(Alert::stable(format!("{dev}: query error")));
             }
         }
     }
@@ -248,7 +316,8 @@ impl App {
 
         let degraded = std::fs::read_to_string(md_path.join("degraded"))?;
         if degraded.trim() != "0" {
-            self.md_warnings.push(format!("md: {dev} DEGRADED"));
+            self.warnings
+                .push(Alert::stable(format!("md: {dev} DEGRADED")));
         }
 
         let num_disks: usize = std::fs::read_to_string(md_path.join("raid_disks"))?
@@ -257,24 +326,260 @@ impl App {
         for i in 0..num_disks {
             let disk_path = md_path.join(format!("rd{i}"));
             if !disk_path.exists() {
-                self.md_errors.push(format!("{dev}: rd{i} NOTFOUND"));
+                self.errors
+                    .push(Alert::stable(format!("{dev}: rd{i} NOTFOUND")));
                 continue;
             }
             let state = std::fs::read_to_string(disk_path.join("state"))?;
             if state.trim() != "in_sync" {
-                self.md_errors.push(format!(
+                self.errors.push(Alert::stable(format!(
                     "{dev}: rd{i} {}",
                     state.trim().to_ascii_uppercase()
-                ));
+                )));
             }
         }
 
         Ok(())
     }
 
+    /// Evaluate the `[alerts]` thresholds against this refresh's polled
+    /// values, appending to the same warning/error sets `check_md_raid`
+    /// populates. Scoped to `config.disk.paths`/`config.network.interfaces`
+    /// the same way the Disk/Network pages are (all of them if the list is
+    /// empty, otherwise just the configured ones), so a mount or interface
+    /// that's filtered out of the display doesn't silently alert anyway.
+    fn evaluate_alerts(&mut self) {
+        let load1 = self.system.load_average().one as f32;
+
+        let total = self.system.total_memory();
+        let mem_used_frac = if total > 0 {
+            (total - self.system.available_memory()) as f32 / total as f32
+        } else {
+            0.0
+        };
+
+        let sorted_disks: BTreeMap<String, &Disk> = self
+            .system
+            .disks()
+            .iter()
+            .map(|disk| (disk.mount_point().to_string_lossy().into_owned(), disk))
+            .collect();
+        let alert_disks: Vec<&Disk> = if self.config.disk.paths.is_empty() {
+            sorted_disks.into_values().collect()
+        } else {
+            self.config
+                .disk
+                .paths
+                .iter()
+                .flat_map(|key| sorted_disks.get(key).copied())
+                .collect()
+        };
+        let disk_used_frac = alert_disks.into_iter().map(|disk| {
+            let mount = disk.mount_point().to_string_lossy().into_owned();
+            let total = disk.total_space();
+            let used_frac = if total > 0 {
+                (total - disk.available_space()) as f32 / total as f32
+            } else {
+                0.0
+            };
+            (mount, used_frac)
+        });
+
+        let sorted_networks: BTreeMap<&String, &NetworkData> =
+            self.system.networks().iter().collect();
+        let alert_networks: Vec<(&String, &NetworkData)> =
+            if self.config.network.interfaces.is_empty() {
+                sorted_networks.into_iter().collect()
+            } else {
+                self.config
+                    .network
+                    .interfaces
+                    .iter()
+                    .flat_map(|key| sorted_networks.get(key).copied().map(|net| (key, net)))
+                    .collect()
+            };
+        let net_mbps = alert_networks.into_iter().map(|(name, net)| {
+            let up = net.transmitted() as f32 / REFRESH_INTERVAL.as_secs_f32() * 8.0e-6;
+            let down = net.received() as f32 / REFRESH_INTERVAL.as_secs_f32() * 8.0e-6;
+            (name.clone(), up.max(down))
+        });
+
+        self.alerts.evaluate(
+            load1,
+            mem_used_frac,
+            disk_used_frac,
+            net_mbps,
+            &mut self.warnings,
+            &mut self.errors,
+        );
+    }
+
+    fn sample_history(&mut self) {
+        let load1 = self.system.load_average().one as f32;
+        self.history.push_load(load1);
+
+        let total = self.system.total_memory();
+        let mem_used_frac = if total > 0 {
+            (total - self.system.available_memory()) as f32 / total as f32
+        } else {
+            0.0
+        };
+        self.history.push_mem_used_frac(mem_used_frac);
+
+        for (name, net) in self.system.networks().iter() {
+            let up = net.transmitted() as f32 / REFRESH_INTERVAL.as_secs_f32() * 8.0e-6;
+            let down = net.received() as f32 / REFRESH_INTERVAL.as_secs_f32() * 8.0e-6;
+            self.history.push_net(name, up, down);
+        }
+    }
+
+    /// Append a journal entry for every warning/error that newly appeared
+    /// or newly cleared since the last refresh, rather than repeating the
+    /// steady-state set every 2 seconds. Transitions are detected by
+    /// `Alert::key` alone, since `Alert::text` for load/mem/disk/net alerts
+    /// embeds a live value that changes on essentially every refresh.
+    ///
+    /// All of this refresh's transitions are `record`ed in memory first and
+    /// the journal is `flush`ed to disk at most once, rather than once per
+    /// transition.
+    fn diff_alerts(&mut self) {
+        let mut dirty = false;
+
+        for alert in &self.errors {
+            if !self.prev_errors.iter().any(|prev| prev.key == alert.key) {
+                self.journal.record(
+                    journal::Severity::Error,
+                    journal::Transition::Raised,
+                    alert.key.clone(),
+                    alert.text.clone(),
+                );
+                dirty = true;
+            }
+        }
+        for alert in &self.prev_errors {
+            if !self.errors.iter().any(|cur| cur.key == alert.key) {
+                self.journal.record(
+                    journal::Severity::Error,
+                    journal::Transition::Cleared,
+                    alert.key.clone(),
+                    alert.text.clone(),
+                );
+                dirty = true;
+            }
+        }
+
+        for alert in &self.warnings {
+            if !self.prev_warnings.iter().any(|prev| prev.key == alert.key) {
+                self.journal.record(
+                    journal::Severity::Warning,
+                    journal::Transition::Raised,
+                    alert.key.clone(),
+                    alert.text.clone(),
+                );
+                dirty = true;
+            }
+        }
+        for alert in &self.prev_warnings {
+            if !self.warnings.iter().any(|cur| cur.key == alert.key) {
+                self.journal.record(
+                    journal::Severity::Warning,
+                    journal::Transition::Cleared,
+                    alert.key.clone(),
+                    alert.text.clone(),
+                );
+                dirty = true;
+            }
+        }
+
+        if dirty {
+            let _ = self.journal.flush();
+        }
+
+        self.prev_errors = self.errors.clone();
+        self.prev_warnings = self.warnings.clone();
+    }
+
+    fn update_snapshot(&mut self) {
+        let load_avg = self.system.load_average();
+        let mut snapshot = exporter::Snapshot {
+            load1: load_avg.one as f32,
+            load5: load_avg.five as f32,
+            load15: load_avg.fifteen as f32,
+            memory_total_bytes: self.system.total_memory() * 1024,
+            memory_used_bytes: (self.system.total_memory() - self.system.available_memory()) * 1024,
+            ..Default::default()
+        };
+
+        for disk in self.system.disks() {
+            let mount = disk.mount_point().to_string_lossy().into_owned();
+            snapshot
+                .disk_total_bytes
+                .insert(mount.clone(), disk.total_space());
+            snapshot
+                .disk_used_bytes
+                .insert(mount, disk.total_space() - disk.available_space());
+        }
+
+        for (name, net) in self.system.networks().iter() {
+            snapshot
+                .net_rx_bytes
+                .insert(name.clone(), net.total_received());
+            snapshot
+                .net_tx_bytes
+                .insert(name.clone(), net.total_transmitted());
+        }
+
+        for dev in &self.config.disk.md_raid {
+            let degraded_path = Path::new("/sys/block/").join(dev).join("md/degraded");
+            let degraded = std::fs::read_to_string(degraded_path)
+                .map(|s| s.trim() != "0")
+                .unwrap_or(false);
+            snapshot.mdraid_degraded.insert(dev.clone(), degraded);
+        }
+
+        *self.snapshot.lock().unwrap() = snapshot;
+    }
+
+    /// Network interface names in the same order/filter as `Page::Network`.
+    fn display_network_names(&self) -> Vec<String> {
+        let sorted_networks: BTreeMap<&String, &NetworkData> =
+            self.system.networks().iter().collect();
+        if self.config.network.interfaces.is_empty() {
+            sorted_networks.into_keys().cloned().collect()
+        } else {
+            self.config.network.interfaces.clone()
+        }
+    }
+
+    /// Metrics selectable on `Page::History`, in cycle order.
+    fn history_sources(&self) -> Vec<HistorySource> {
+        let mut sources = vec![HistorySource::Load, HistorySource::Memory];
+        for name in self.display_network_names() {
+            sources.push(HistorySource::NetUp(name.clone()));
+            sources.push(HistorySource::NetDown(name));
+        }
+        sources
+    }
+
+    fn history_next_metric(&mut self) {
+        let count = self.history_sources().len();
+        if count > 0 {
+            self.history_metric = (self.history_metric + 1) % count;
+            self.queue_redraw();
+        }
+    }
+
+    fn history_prev_metric(&mut self) {
+        let count = self.history_sources().len();
+        if count > 0 {
+            self.history_metric = (self.history_metric + count - 1) % count;
+            self.queue_redraw();
+        }
+    }
+
     fn redraw(&mut self) -> anyhow::Result<()> {
         self.clear();
-        if self.current_page != Page::Messages {
+        if self.current_page != Page::Messages && self.current_page != Page::Journal {
             if let Some(name) = self.system.host_name() {
                 self.set_text(0, 0, name.as_bytes());
             }
@@ -282,17 +587,35 @@ impl App {
 
         match self.current_page {
             Page::Messages => {
-                let max_scroll = (self.md_errors.len() + self.md_warnings.len())
+                let max_scroll = (self.errors.len() + self.warnings.len())
                     .saturating_sub(NUM_ROWS as usize - 1);
                 self.max_scroll = Some(max_scroll);
                 self.scroll = self.scroll.min(max_scroll);
                 let lines: Vec<String> = self
-                    .md_errors
+                    .errors
                     .iter()
-                    .chain(&self.md_warnings)
+                    .chain(&self.warnings)
                     .skip(self.scroll)
                     .take(NUM_ROWS as usize - 1)
-                    .cloned()
+                    .map(|alert| alert.text.clone())
+                    .collect();
+
+                for (i, line) in lines.into_iter().enumerate() {
+                    self.set_text(i, 0, line.as_bytes());
+                }
+            }
+            Page::Journal => {
+                let now = journal::unix_timestamp();
+                let entries: Vec<&journal::Entry> = self.journal.entries().rev().collect();
+                let max_scroll = entries.len().saturating_sub(NUM_ROWS as usize);
+                self.max_scroll = Some(max_scroll);
+                self.scroll = self.scroll.min(max_scroll);
+
+                let lines: Vec<String> = entries
+                    .into_iter()
+                    .skip(self.scroll)
+                    .take(NUM_ROWS as usize)
+                    .map(|entry| format_journal_line(entry, now))
                     .collect();
 
                 for (i, line) in lines.into_iter().enumerate() {
@@ -395,6 +718,44 @@ impl App {
                     self.set_text(i + 1, 0, line.as_bytes());
                 }
             }
+            Page::History => {
+                let sources = self.history_sources();
+                let index = self.history_metric.min(sources.len().saturating_sub(1));
+                if let Some(source) = sources.get(index) {
+                    let (label, samples, max) = match source {
+                        HistorySource::Load => (
+                            "Load".to_owned(),
+                            self.history.load.clone(),
+                            self.system.cpus().len().max(1) as f32,
+                        ),
+                        HistorySource::Memory => (
+                            "Mem".to_owned(),
+                            self.history.mem_used_frac.clone(),
+                            1.0,
+                        ),
+                        HistorySource::NetUp(name) => (
+                            format!("{name} Up"),
+                            self.history
+                                .net
+                                .get(name)
+                                .map(|h| h.up.clone())
+                                .unwrap_or_default(),
+                            self.config.network.link_mbps,
+                        ),
+                        HistorySource::NetDown(name) => (
+                            format!("{name} Down"),
+                            self.history
+                                .net
+                                .get(name)
+                                .map(|h| h.down.clone())
+                                .unwrap_or_default(),
+                            self.config.network.link_mbps,
+                        ),
+                    };
+                    self.set_text(1, 0, label.as_bytes());
+                    self.set_text(2, 0, &history::render_row(&samples, max));
+                }
+            }
         }
         self.flush()?;
         // Deferred backlight control from wake():
@@ -418,9 +779,30 @@ impl App {
     }
 
     fn flush(&mut self) -> anyhow::Result<()> {
-        for (row_index, row_text) in self.buffer.iter().enumerate() {
-            self.lcd.set_text(row_index as u8, 0, row_text)?;
+        for row in 0..NUM_ROWS as usize {
+            let buffer_row = &self.buffer[row];
+            let displayed_row = &self.displayed[row];
+            if buffer_row == displayed_row {
+                continue;
+            }
+
+            // Only the minimal contiguous span that actually changed needs
+            // to go over the wire.
+            let first_diff = buffer_row
+                .iter()
+                .zip(displayed_row)
+                .position(|(a, b)| a != b)
+                .expect("rows differ, so there is at least one differing cell");
+            let last_diff = buffer_row
+                .iter()
+                .zip(displayed_row)
+                .rposition(|(a, b)| a != b)
+                .expect("rows differ, so there is at least one differing cell");
+
+            self.lcd
+                .set_text(row as u8, first_diff as u8, &buffer_row[first_diff..=last_diff])?;
         }
+        self.displayed = self.buffer;
         Ok(())
     }
 }
@@ -428,42 +810,80 @@ impl App {
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Page {
     Messages,
+    Journal,
     System,
     Disk,
     Network,
+    History,
 }
 
 impl Page {
     fn next(&self) -> Self {
         match self {
-            // Messages cannot be dismissed by page select.
+            // Messages/Journal cannot be dismissed by page select.
             Self::Messages => Self::Messages,
+            Self::Journal => Self::Journal,
             Self::System => Self::Disk,
             Self::Disk => Self::Network,
-            Self::Network => Self::System,
+            Self::Network => Self::History,
+            Self::History => Self::System,
         }
     }
 
     fn prev(&self) -> Self {
         match self {
-            // Messages cannot be dismissed by page select.
+            // Messages/Journal cannot be dismissed by page select.
             Self::Messages => Self::Messages,
+            Self::Journal => Self::Journal,
             Self::Disk => Self::System,
             Self::Network => Self::Disk,
-            Self::System => Self::Network,
+            Self::History => Self::Network,
+            Self::System => Self::History,
         }
     }
 }
 
+/// A metric selectable on `Page::History` via Up/Down.
+#[derive(Debug, Clone)]
+enum HistorySource {
+    Load,
+    Memory,
+    NetUp(String),
+    NetDown(String),
+}
+
 fn main() -> anyhow::Result<()> {
     let config_raw =
         std::fs::read("ServerHUD.toml").context("cannot read config file ServerHUD.toml")?;
     let config: Config = toml::from_slice(&config_raw).context("cannot parse config file")?;
 
     let app = App::new(config)?;
+    if let Some(exporter_config) = &app.config.exporter {
+        exporter::spawn(exporter_config.listen.clone(), Arc::clone(&app.snapshot))?;
+    }
     app.run()
 }
 
 fn kb_to_mib(x: u64) -> u64 {
     x * 1024 / 1000 / 1024
 }
+
+/// Render a journal entry as `<marker><age> <text>`, clipped to fit by
+/// `set_text` like every other line on the panel.
+fn format_journal_line(entry: &journal::Entry, now: u64) -> String {
+    let age = now.saturating_sub(entry.timestamp);
+    let age = if age < 60 {
+        format!("{age}s")
+    } else if age < 3600 {
+        format!("{}m", age / 60)
+    } else if age < 86400 {
+        format!("{}h", age / 3600)
+    } else {
+        format!("{}d", age / 86400)
+    };
+    let marker = match entry.transition {
+        journal::Transition::Raised => '+',
+        journal::Transition::Cleared => '-',
+    };
+    format!("{marker}{age:>3} {}", entry.text)
+}